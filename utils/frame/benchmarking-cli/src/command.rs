@@ -0,0 +1,159 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use sc_cli::{ExecutionStrategy, Result, SharedParams, WasmExecutionMethod};
+use structopt::StructOpt;
+
+use frame_benchmarking::{BenchmarkBatch, StorageInfo};
+
+use crate::writer::{self, AnalysisChoice, OutputFormat, PovEstimationMode, PovModesMap};
+
+/// Benchmark the extrinsic weight of FRAME pallets.
+#[derive(Debug, StructOpt)]
+pub struct BenchmarkCmd {
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	/// Select a FRAME Pallet to benchmark, or `*` for all (in which case `extrinsic` must be `*`).
+	#[structopt(short, long)]
+	pub pallet: String,
+
+	/// Select an extrinsic inside the pallet to benchmark, or `*` for all.
+	#[structopt(short, long)]
+	pub extrinsic: String,
+
+	/// Select how many samples we should take across the variable components.
+	#[structopt(short, long, use_delimiter = true, default_value = "1")]
+	pub steps: Vec<u32>,
+
+	/// Indicates lowest values for each of the component ranges.
+	#[structopt(long = "low", use_delimiter = true)]
+	pub lowest_range_values: Vec<u32>,
+
+	/// Indicates highest values for each of the component ranges.
+	#[structopt(long = "high", use_delimiter = true)]
+	pub highest_range_values: Vec<u32>,
+
+	/// Select how many repetitions of this benchmark should run.
+	#[structopt(short, long, default_value = "1")]
+	pub repeat: u32,
+
+	/// The execution strategy that should be used for benchmarks.
+	#[structopt(long = "execution", value_name = "STRATEGY")]
+	pub execution: Option<ExecutionStrategy>,
+
+	/// Method used to execute Wasm smart contracts.
+	#[structopt(long = "wasm-execution", value_name = "METHOD", default_value = "compiled")]
+	pub wasm_method: WasmExecutionMethod,
+
+	/// Limit the memory the database cache can use.
+	#[structopt(long = "db-cache", value_name = "MiB", default_value = "1024")]
+	pub database_cache_size: u32,
+
+	/// Where to output the benchmark results.
+	///
+	/// A directory for the per-pallet Rust files, or, combined with `--output-format`, a file
+	/// path for the JSON/CSV dump.
+	#[structopt(long)]
+	pub output: Option<PathBuf>,
+
+	/// Header file to prepend to the generated output files.
+	#[structopt(long)]
+	pub header: Option<PathBuf>,
+
+	/// Custom Handlebars template to use for the generated `.rs` files.
+	#[structopt(long)]
+	pub template: Option<PathBuf>,
+
+	/// Which regression strategy to fit the raw samples with: `min-squares`, `median-slopes`, or
+	/// `max-observed`. See [`AnalysisChoice`] for what each one trades off.
+	#[structopt(long, default_value = "min-squares")]
+	pub analysis_choice: AnalysisChoice,
+
+	/// Output format for the generated results: `rust` (the default per-pallet `.rs` files),
+	/// `json`, or `csv`. JSON/CSV skip Handlebars entirely, for CI pipelines and archiving.
+	#[structopt(long, default_value = "rust")]
+	pub output_format: OutputFormat,
+
+	/// Minimum acceptable R² for a used component's fitted regression line. A benchmark whose
+	/// fit falls below this is only warned about, unless `--strict` is also set.
+	#[structopt(long, default_value = "0.95")]
+	pub r_squared_threshold: f64,
+
+	/// Fail instead of warning when a used component's regression R² falls below
+	/// `--r-squared-threshold`.
+	#[structopt(long)]
+	pub strict: bool,
+
+	/// Path to a previous run's JSON benchmark archive (as produced by `--output-format json`)
+	/// to compare this run against. When set, this run gates on regressions instead of writing
+	/// new results: per-pallet `.rs` files, headers, and templates are not produced.
+	///
+	/// Note this must be a JSON archive, not a directory of generated `.rs` files - those don't
+	/// carry structured base weight/slope data to diff against.
+	#[structopt(long)]
+	pub old_results: Option<PathBuf>,
+
+	/// Maximum allowed percentage increase in base weight or a used component's slope, relative
+	/// to `--old-results`, before a benchmark is flagged as a regression.
+	#[structopt(long, default_value = "5.0")]
+	pub regression_threshold: f64,
+}
+
+impl BenchmarkCmd {
+	/// Classify every storage prefix touched by `storage_info` into a [`PovEstimationMode`] and
+	/// write the freshly computed `batches` out according to this command's output options.
+	///
+	/// `storage_info` mirrors `frame_support::storage::info::PrefixedStorageInfo` as reported by
+	/// the runtime under benchmark: a prefix with a statically known `max_size` is classified as
+	/// `MaxEncodedLen`, everything else as `Measured`. The modes are passed on as labels only; the
+	/// proof-size weights are fitted from the measured proof size in either case, and no
+	/// `max_size` bound is applied to them. `StorageInfo` is pallet-wide metadata with no notion
+	/// of which benchmark touches which prefix, so modes are keyed by pallet only and every
+	/// benchmark in that pallet is given the same set of modes.
+	pub fn run(&self, batches: &[BenchmarkBatch], storage_info: &[StorageInfo]) -> Result<()> {
+		let mut pov_modes = PovModesMap::new();
+		for info in storage_info {
+			let prefixes = pov_modes.entry(info.pallet_name.clone()).or_insert_with(HashMap::new);
+			let mode = if info.max_size.is_some() {
+				PovEstimationMode::MaxEncodedLen
+			} else {
+				PovEstimationMode::Measured
+			};
+			prefixes.insert(info.prefix.clone(), mode);
+		}
+
+		if let Some(old_results_path) = &self.old_results {
+			let within_threshold = writer::compare_results(
+				batches, &pov_modes, old_results_path, self, self.regression_threshold,
+			).map_err(|e| sc_cli::Error::Application(e.into()))?;
+			return if within_threshold {
+				Ok(())
+			} else {
+				Err(sc_cli::Error::Application("one or more benchmarks regressed beyond the configured threshold".into()))
+			}
+		}
+
+		let output_path = self.output.clone().unwrap_or_else(|| PathBuf::from("."));
+		writer::write_results(batches, &pov_modes, &output_path, self)
+			.map_err(|e| sc_cli::Error::Application(e.into()))
+	}
+}
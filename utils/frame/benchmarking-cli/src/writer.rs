@@ -17,11 +17,11 @@
 
 // Outputs benchmark results to Rust files that can be ingested by the runtime.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::PathBuf;
 
-use serde::Serialize;
+use serde::{Serialize, Deserialize};
 
 use crate::BenchmarkCmd;
 use frame_benchmarking::{BenchmarkBatch, BenchmarkSelector, Analysis};
@@ -44,16 +44,131 @@ struct TemplateData {
 }
 
 // This was the final data we have about each benchmark.
-#[derive(Serialize, Default, Debug, Clone)]
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
 struct BenchmarkData {
 	name: String,
 	components: Vec<Component>,
 	base_weight: u128,
 	base_reads: u128,
 	base_writes: u128,
+	base_proof_size: u128,
 	component_weight: Vec<ComponentSlope>,
 	component_reads: Vec<ComponentSlope>,
 	component_writes: Vec<ComponentSlope>,
+	component_proof_size: Vec<ComponentSlope>,
+	// Map from storage prefix of this benchmark's pallet to its PoV mode, as a label. Only
+	// contains prefixes whose mode is not `Ignored`. The mode does not change
+	// `base_proof_size` or `component_proof_size`, which are fitted from the measured proof size.
+	pov_modes: HashMap<String, String>,
+	// Goodness-of-fit of the fitted regression line against the observed samples, one per
+	// dimension, so a generated weight can carry a `R² = 0.97, σ = 1.2µs`-style comment.
+	weight_quality: RegressionQuality,
+	reads_quality: RegressionQuality,
+	writes_quality: RegressionQuality,
+	proof_size_quality: RegressionQuality,
+}
+
+// The method used to estimate the proof size (PoV) contributed by a single storage prefix
+// touched during a benchmark. This mirrors `frame_benchmarking::PovEstimationMode`. The writer
+// only records each prefix's mode for the template; the proof-size regression always uses the
+// measured proof size, whatever the mode.
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+pub(crate) enum PovEstimationMode {
+	/// Use the proof size that was actually measured while benchmarking.
+	Measured,
+	/// Use the maximum possible encoded length of the storage item (`MaxEncodedLen`) as a
+	/// static upper bound, regardless of what was measured.
+	MaxEncodedLen,
+	/// Do not charge any proof size weight for this storage prefix at all.
+	Ignored,
+}
+
+impl std::fmt::Display for PovEstimationMode {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			PovEstimationMode::Measured => write!(f, "Measured"),
+			PovEstimationMode::MaxEncodedLen => write!(f, "MaxEncodedLen"),
+			PovEstimationMode::Ignored => write!(f, "Ignored"),
+		}
+	}
+}
+
+// Map from pallet name to the PoV mode of each storage prefix that pallet touches. Keyed by
+// pallet only, not by (pallet, benchmark): `StorageInfo` is pallet-wide storage metadata with no
+// notion of which benchmark touches which prefix, so every benchmark in a pallet is given the
+// same set of modes for that pallet's storage.
+pub(crate) type PovModesMap = HashMap<String, HashMap<String, PovEstimationMode>>;
+
+// Which statistical regression strategy to fit a benchmark's raw samples with. Selected with
+// the `--analysis-choice` CLI option and forwarded into `get_benchmark_data`.
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+pub enum AnalysisChoice {
+	/// Ordinary least-squares regression with interquartile-range outlier filtering.
+	MinSquares,
+	/// A robust median-of-pairwise-slopes estimator, resistant to a handful of outlying samples.
+	MedianSlopes,
+	/// A pure worst-case bound: the base weight is the maximum observed sample and every slope
+	/// is zero.
+	MaxObserved,
+}
+
+impl Default for AnalysisChoice {
+	fn default() -> Self {
+		AnalysisChoice::MinSquares
+	}
+}
+
+impl std::str::FromStr for AnalysisChoice {
+	type Err = &'static str;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"min-squares" => Ok(Self::MinSquares),
+			"median-slopes" => Ok(Self::MedianSlopes),
+			"max-observed" => Ok(Self::MaxObserved),
+			_ => Err("invalid analysis choice, expected one of: min-squares, median-slopes, max-observed"),
+		}
+	}
+}
+
+impl std::fmt::Display for AnalysisChoice {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Self::MinSquares => write!(f, "min-squares"),
+			Self::MedianSlopes => write!(f, "median-slopes"),
+			Self::MaxObserved => write!(f, "max-observed"),
+		}
+	}
+}
+
+// The output format for the generated results, selected with the `--output-format` CLI option.
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+pub enum OutputFormat {
+	/// Render the Handlebars template into one `.rs` file per pallet. The default.
+	Rust,
+	/// Dump every pallet's `BenchmarkData` as a single structured JSON document.
+	Json,
+	/// Flatten every benchmarked component into one CSV row.
+	Csv,
+}
+
+impl Default for OutputFormat {
+	fn default() -> Self {
+		OutputFormat::Rust
+	}
+}
+
+impl std::str::FromStr for OutputFormat {
+	type Err = &'static str;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"rust" => Ok(Self::Rust),
+			"json" => Ok(Self::Json),
+			"csv" => Ok(Self::Csv),
+			_ => Err("invalid output format, expected one of: rust, json, csv"),
+		}
+	}
 }
 
 // This forwards some specific metadata from the `BenchmarkCmd`
@@ -67,17 +182,18 @@ struct CmdData {
 	wasm_execution: String,
 	chain: String,
 	db_cache: u32,
+	analysis_choice: String,
 }
 
 // This encodes the component name and whether that component is used.
-#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 struct Component {
 	name: String,
 	is_used: bool,
 }
 
 // This encodes the slope of some benchmark related to a component.
-#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 struct ComponentSlope {
 	name: String,
 	slope: u128,
@@ -97,7 +213,11 @@ fn io_error(s: &str) -> std::io::Error {
 // p1 -> [b1, b2, b3]
 // p2 -> [b1, b2]
 // ```
-fn map_results(batches: &[BenchmarkBatch]) -> Result<HashMap<String, HashMap<String, BenchmarkData>>, std::io::Error> {
+fn map_results(
+	batches: &[BenchmarkBatch],
+	pov_modes: &PovModesMap,
+	analysis_choice: &AnalysisChoice,
+) -> Result<HashMap<String, HashMap<String, BenchmarkData>>, std::io::Error> {
 	// Skip if batches is empty.
 	if batches.is_empty() { return Err(io_error("empty batches")) }
 
@@ -112,7 +232,9 @@ fn map_results(batches: &[BenchmarkBatch]) -> Result<HashMap<String, HashMap<Str
 		let pallet_string = String::from_utf8(batch.pallet.clone()).unwrap();
 		let benchmark_string = String::from_utf8(batch.benchmark.clone()).unwrap();
 
-		let benchmark_data = get_benchmark_data(batch);
+		let empty_modes = HashMap::new();
+		let modes = pov_modes.get(&pallet_string).unwrap_or(&empty_modes);
+		let benchmark_data = get_benchmark_data(batch, modes, analysis_choice);
 		pallet_map.insert(benchmark_string, benchmark_data);
 
 		// Check if this is the end of the iterator
@@ -131,18 +253,178 @@ fn map_results(batches: &[BenchmarkBatch]) -> Result<HashMap<String, HashMap<Str
 	Ok(all_benchmarks)
 }
 
+// The result of a regression over a single cost dimension: a base constant plus one slope
+// per component, aligned by index with `names`. Shaped to match what
+// `frame_benchmarking::Analysis::min_squares_iqr` returns, so all three strategies can be
+// consumed identically downstream.
+struct RegressionResult {
+	base: u128,
+	slopes: Vec<u128>,
+	names: Vec<String>,
+}
+
+// Goodness-of-fit of a fitted `base + Σ slope_c * x_c` regression line against the samples it
+// was fit from: the coefficient of determination (R², 1.0 is a perfect fit) and the standard
+// error of the estimate, in the same units as the dimension being measured.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy)]
+struct RegressionQuality {
+	r_squared: f64,
+	standard_error: f64,
+}
+
+// Compute R² and standard error of `fit` against the samples it was derived from, regardless
+// of which regression strategy produced it.
+fn regression_quality(
+	results: &[frame_benchmarking::BenchmarkResults],
+	selector: BenchmarkSelector,
+	fit: &RegressionResult,
+) -> RegressionQuality {
+	let observed = results.iter().map(|r| selector_value(r, selector) as f64).collect::<Vec<_>>();
+	let predicted = results.iter().map(|r| {
+		fit.base as f64 + fit.names.iter().zip(fit.slopes.iter())
+			.map(|(name, slope)| component_value(r, name) as f64 * *slope as f64)
+			.sum::<f64>()
+	}).collect::<Vec<_>>();
+
+	let n = observed.len() as f64;
+	if n == 0.0 { return RegressionQuality::default() }
+
+	let mean = observed.iter().sum::<f64>() / n;
+	let ss_tot: f64 = observed.iter().map(|y| (y - mean).powi(2)).sum();
+	let ss_res: f64 = observed.iter().zip(predicted.iter()).map(|(y, y_hat)| (y - y_hat).powi(2)).sum();
+
+	let r_squared = if ss_tot == 0.0 { 1.0 } else { 1.0 - ss_res / ss_tot };
+	// Degrees of freedom: one per fitted slope, plus one for the base term.
+	let degrees_of_freedom = (n - fit.slopes.len() as f64 - 1.0).max(1.0);
+	let standard_error = (ss_res / degrees_of_freedom).sqrt();
+
+	RegressionQuality { r_squared, standard_error }
+}
+
+// Read the value of the given cost dimension out of a single result point.
+fn selector_value(result: &frame_benchmarking::BenchmarkResults, selector: BenchmarkSelector) -> u128 {
+	match selector {
+		BenchmarkSelector::ExtrinsicTime => result.extrinsic_time as u128,
+		BenchmarkSelector::Reads => result.reads as u128,
+		BenchmarkSelector::Writes => result.writes as u128,
+		BenchmarkSelector::ProofSize => result.proof_size as u128,
+	}
+}
+
+// Names of every component observed across a benchmark's result points, in first-seen order.
+fn component_names(results: &[frame_benchmarking::BenchmarkResults]) -> Vec<String> {
+	let mut names = Vec::new();
+	for result in results {
+		for (param, _) in &result.components {
+			let name = param.to_string();
+			if !names.contains(&name) { names.push(name); }
+		}
+	}
+	names
+}
+
+fn component_value(result: &frame_benchmarking::BenchmarkResults, name: &str) -> u128 {
+	result.components.iter()
+		.find(|(param, _)| param.to_string() == name)
+		.map(|(_, value)| *value as u128)
+		.unwrap_or(0)
+}
+
+// Median of a list of values, averaging the two middle elements on an even-length list.
+fn median_i128(mut values: Vec<i128>) -> i128 {
+	if values.is_empty() { return 0 }
+	values.sort();
+	let len = values.len();
+	if len % 2 == 1 {
+		values[len / 2]
+	} else {
+		(values[len / 2 - 1] + values[len / 2]) / 2
+	}
+}
+
+// A robust regression estimator: for each component, take the median of every pairwise slope
+// `(y_j - y_i) / (x_j - x_i)` over result points whose value for that component differs,
+// saturating negative slopes to zero. The base term is then the median residual
+// `y_k - Σ slope_c * x_{c,k}` across all points. This is far more resistant to a handful of
+// outlying (e.g. unusually slow) samples than ordinary least squares.
+fn median_slopes(results: &[frame_benchmarking::BenchmarkResults], selector: BenchmarkSelector) -> RegressionResult {
+	let names = component_names(results);
+
+	let slopes = names.iter().map(|name| {
+		let mut pairwise_slopes = Vec::new();
+		for i in 0 .. results.len() {
+			for j in (i + 1) .. results.len() {
+				let x_i = component_value(&results[i], name) as i128;
+				let x_j = component_value(&results[j], name) as i128;
+				if x_i == x_j { continue }
+				let y_i = selector_value(&results[i], selector) as i128;
+				let y_j = selector_value(&results[j], selector) as i128;
+				pairwise_slopes.push((y_j - y_i) / (x_j - x_i));
+			}
+		}
+		median_i128(pairwise_slopes).max(0) as u128
+	}).collect::<Vec<_>>();
+
+	let residuals = results.iter().map(|result| {
+		let y = selector_value(result, selector) as i128;
+		let predicted: i128 = names.iter().zip(slopes.iter())
+			.map(|(name, slope)| component_value(result, name) as i128 * *slope as i128)
+			.sum();
+		y - predicted
+	}).collect::<Vec<_>>();
+	let base = median_i128(residuals).max(0) as u128;
+
+	RegressionResult { base, slopes, names }
+}
+
+// A pure worst-case bound: base is the maximum observed sample and every slope is zero, i.e.
+// the benchmark is treated as constant-cost at its slowest measured sample.
+fn max_observed(results: &[frame_benchmarking::BenchmarkResults], selector: BenchmarkSelector) -> RegressionResult {
+	let names = component_names(results);
+	let base = results.iter().map(|r| selector_value(r, selector)).max().unwrap_or(0);
+	RegressionResult { base, slopes: vec![0; names.len()], names }
+}
+
+// Fit `results` over `selector` using whichever regression strategy was requested.
+fn analyze(
+	choice: &AnalysisChoice,
+	results: &[frame_benchmarking::BenchmarkResults],
+	selector: BenchmarkSelector,
+) -> RegressionResult {
+	match choice {
+		AnalysisChoice::MinSquares => {
+			let analysis = Analysis::min_squares_iqr(results, selector).unwrap();
+			RegressionResult { base: analysis.base, slopes: analysis.slopes, names: analysis.names }
+		},
+		AnalysisChoice::MedianSlopes => median_slopes(results, selector),
+		AnalysisChoice::MaxObserved => max_observed(results, selector),
+	}
+}
+
 // Analyze and return the relevant results for a given benchmark.
-fn get_benchmark_data(batch: &BenchmarkBatch) -> BenchmarkData {
-	// Analyze benchmarks to get the linear regression.
-	let extrinsic_time = Analysis::min_squares_iqr(&batch.results, BenchmarkSelector::ExtrinsicTime).unwrap();
-	let reads = Analysis::min_squares_iqr(&batch.results, BenchmarkSelector::Reads).unwrap();
-	let writes = Analysis::min_squares_iqr(&batch.results, BenchmarkSelector::Writes).unwrap();
+fn get_benchmark_data(
+	batch: &BenchmarkBatch,
+	pov_modes: &HashMap<String, PovEstimationMode>,
+	analysis_choice: &AnalysisChoice,
+) -> BenchmarkData {
+	// Run the chosen regression strategy over each cost dimension.
+	let extrinsic_time = analyze(analysis_choice, &batch.results, BenchmarkSelector::ExtrinsicTime);
+	let reads = analyze(analysis_choice, &batch.results, BenchmarkSelector::Reads);
+	let writes = analyze(analysis_choice, &batch.results, BenchmarkSelector::Writes);
+	let proof_size = analyze(analysis_choice, &batch.results, BenchmarkSelector::ProofSize);
+
+	// Capture goodness-of-fit before the regression results below are consumed.
+	let weight_quality = regression_quality(&batch.results, BenchmarkSelector::ExtrinsicTime, &extrinsic_time);
+	let reads_quality = regression_quality(&batch.results, BenchmarkSelector::Reads, &reads);
+	let writes_quality = regression_quality(&batch.results, BenchmarkSelector::Writes, &writes);
+	let proof_size_quality = regression_quality(&batch.results, BenchmarkSelector::ProofSize, &proof_size);
 
 	// Analysis data may include components that are not used, this filters out anything whose value is zero.
 	let mut used_components = Vec::new();
 	let mut used_extrinsic_time = Vec::new();
 	let mut used_reads = Vec::new();
 	let mut used_writes = Vec::new();
+	let mut used_proof_size = Vec::new();
 
 	extrinsic_time.slopes.into_iter().zip(extrinsic_time.names.iter()).for_each(|(slope, name)| {
 		if !slope.is_zero() {
@@ -165,6 +447,12 @@ fn get_benchmark_data(batch: &BenchmarkBatch) -> BenchmarkData {
 			used_writes.push(ComponentSlope { name: name.clone(), slope });
 		}
 	});
+	proof_size.slopes.into_iter().zip(proof_size.names.iter()).for_each(|(slope, name)| {
+		if !slope.is_zero() {
+			if !used_components.contains(&name) { used_components.push(name); }
+			used_proof_size.push(ComponentSlope { name: name.clone(), slope });
+		}
+	});
 
 	// This puts a marker on any component which is entirely unused in the weight formula.
 	let components = batch.results[0].components
@@ -176,24 +464,54 @@ fn get_benchmark_data(batch: &BenchmarkBatch) -> BenchmarkData {
 		})
 		.collect::<Vec<_>>();
 
+	// `Ignored` prefixes are dropped entirely rather than surfaced to the template. The fitted
+	// proof size above is not adjusted for them.
+	let pov_modes = pov_modes.iter()
+		.filter(|(_, mode)| **mode != PovEstimationMode::Ignored)
+		.map(|(prefix, mode)| (prefix.clone(), mode.to_string()))
+		.collect();
+
 	BenchmarkData {
 		name: String::from_utf8(batch.benchmark.clone()).unwrap(),
 		components,
 		base_weight: extrinsic_time.base.saturating_mul(1000),
 		base_reads: reads.base,
 		base_writes: writes.base,
+		base_proof_size: proof_size.base,
 		component_weight: used_extrinsic_time,
 		component_reads: used_reads,
 		component_writes: used_writes,
+		component_proof_size: used_proof_size,
+		pov_modes,
+		weight_quality,
+		reads_quality,
+		writes_quality,
+		proof_size_quality,
 	}
 }
 
 // Create weight file from benchmark data and Handlebars template.
 pub fn write_results(
 	batches: &[BenchmarkBatch],
+	pov_modes: &PovModesMap,
 	path: &PathBuf,
 	cmd: &BenchmarkCmd,
 ) -> Result<(), std::io::Error> {
+	// Organize results by pallet into a JSON map
+	let all_results = map_results(batches, pov_modes, &cmd.analysis_choice)?;
+
+	// Flag (or, under `--strict`, fail) any used component whose linear model doesn't explain
+	// the observed samples well, regardless of which output format was requested.
+	check_regression_quality(&all_results, cmd.r_squared_threshold, cmd.strict)?;
+
+	// The machine-readable formats skip Handlebars entirely and dump the same `BenchmarkData`
+	// that would otherwise be fed into the template, for CI pipelines and archiving.
+	match cmd.output_format {
+		OutputFormat::Json => return write_results_json(&all_results, path),
+		OutputFormat::Csv => return write_results_csv(&all_results, path),
+		OutputFormat::Rust => {},
+	}
+
 	// Use custom template if provided.
 	let template: String = match &cmd.template {
 		Some(template_file) => {
@@ -230,6 +548,7 @@ pub fn write_results(
 		wasm_execution: cmd.wasm_method.to_string(),
 		chain: format!("{:?}", cmd.shared_params.chain),
 		db_cache: cmd.database_cache_size,
+		analysis_choice: cmd.analysis_choice.to_string(),
 	};
 
 	// New Handlebars instance with helpers.
@@ -237,8 +556,9 @@ pub fn write_results(
 	handlebars.register_helper("underscore", Box::new(UnderscoreHelper));
 	handlebars.register_helper("join", Box::new(JoinHelper));
 
-	// Organize results by pallet into a JSON map
-	let all_results = map_results(batches)?;
+	// Alongside the per-pallet Rust files, also render a consolidated Markdown report.
+	write_markdown_report(&all_results, path)?;
+
 	for (pallet, results) in all_results.into_iter() {
 		// Create new file: "path/to/pallet_name.rs".
 		let mut file_path = path.clone();
@@ -264,6 +584,311 @@ pub fn write_results(
 	Ok(())
 }
 
+// Dump every pallet's `BenchmarkData` as a single structured JSON document, so CI tooling can
+// ingest raw benchmark numbers directly instead of parsing generated Rust.
+fn write_results_json(
+	all_results: &HashMap<String, HashMap<String, BenchmarkData>>,
+	path: &PathBuf,
+) -> Result<(), std::io::Error> {
+	let mut file_path = path.clone();
+	if file_path.file_name().is_none() {
+		file_path.push("benchmarks");
+	}
+	file_path.set_extension("json");
+
+	// `HashMap` iterates in an unspecified, hash-randomized order, which would make two JSON
+	// archives of the same run diff spuriously on key order alone. Sort into `BTreeMap`s so the
+	// archive is byte-for-byte stable across runs.
+	let sorted_results = all_results.iter()
+		.map(|(pallet, benchmarks)| (pallet, benchmarks.iter().collect::<BTreeMap<_, _>>()))
+		.collect::<BTreeMap<_, _>>();
+
+	let file = fs::File::create(file_path)?;
+	serde_json::to_writer_pretty(file, &sorted_results).map_err(|e| io_error(&e.to_string()))
+}
+
+// Flatten every benchmarked component's slope into one CSV row. A benchmark is spread over one
+// row per cost dimension (`ref_time`, `reads`, `writes`, `proof_size`) per used component, since
+// a component can drive a reads or writes slope without driving a ref_time slope at all. A
+// dimension with no used component still gets one row, with empty `component` and `slope`.
+fn write_results_csv(
+	all_results: &HashMap<String, HashMap<String, BenchmarkData>>,
+	path: &PathBuf,
+) -> Result<(), std::io::Error> {
+	use std::io::Write;
+
+	let mut file_path = path.clone();
+	if file_path.file_name().is_none() {
+		file_path.push("benchmarks");
+	}
+	file_path.set_extension("csv");
+
+	let mut file = fs::File::create(file_path)?;
+	writeln!(file, "pallet,benchmark,dimension,component,base,slope")?;
+
+	// `HashMap` iterates in an unspecified, hash-randomized order, which would make two CSV
+	// archives of the same run diff spuriously on row order alone, so sort pallets and
+	// benchmarks the same way `write_markdown_report` does.
+	let mut pallets = all_results.keys().collect::<Vec<_>>();
+	pallets.sort();
+	for pallet in pallets {
+		let benchmarks = &all_results[pallet];
+		let mut benchmark_names = benchmarks.keys().collect::<Vec<_>>();
+		benchmark_names.sort();
+		for benchmark in benchmark_names {
+			let data = &benchmarks[benchmark];
+			let dimensions = [
+				("ref_time", &data.component_weight, data.base_weight),
+				("reads", &data.component_reads, data.base_reads),
+				("writes", &data.component_writes, data.base_writes),
+				("proof_size", &data.component_proof_size, data.base_proof_size),
+			];
+			for (dimension, slopes, base) in dimensions {
+				if slopes.is_empty() {
+					writeln!(file, "{},{},{},,{},", pallet, benchmark, dimension, base)?;
+					continue
+				}
+				for slope in slopes {
+					writeln!(
+						file, "{},{},{},{},{},{}",
+						pallet, benchmark, dimension, slope.name, base, slope.slope,
+					)?;
+				}
+			}
+		}
+	}
+	Ok(())
+}
+
+// Warn (or, under `--strict`, fail) when a used component's fitted regression line has an R²
+// below `threshold`, so a poorly-fit weight doesn't pass silently as a confident one.
+fn check_regression_quality(
+	all_results: &HashMap<String, HashMap<String, BenchmarkData>>,
+	threshold: f64,
+	strict: bool,
+) -> Result<(), std::io::Error> {
+	let mut failures = Vec::new();
+	for (pallet, benchmarks) in all_results {
+		for (benchmark, data) in benchmarks {
+			let dimensions = [
+				("ref_time", &data.component_weight, &data.weight_quality),
+				("reads", &data.component_reads, &data.reads_quality),
+				("writes", &data.component_writes, &data.writes_quality),
+				("proof_size", &data.component_proof_size, &data.proof_size_quality),
+			];
+			for (dimension, used_components, quality) in dimensions {
+				// Only a dimension with at least one used component can produce an
+				// untrustworthy weight; an all-zero slope doesn't depend on the fit at all.
+				if used_components.is_empty() { continue }
+				if quality.r_squared < threshold {
+					failures.push(format!(
+						"{}::{}: {} regression has R\u{b2} = {:.2} (below threshold {:.2})",
+						pallet, benchmark, dimension, quality.r_squared, threshold,
+					));
+				}
+			}
+		}
+	}
+
+	for failure in &failures {
+		println!("warning: {}", failure);
+	}
+
+	if strict && !failures.is_empty() {
+		return Err(io_error(&format!("{} benchmark(s) failed the R\u{b2} quality gate", failures.len())))
+	}
+	Ok(())
+}
+
+// Render one consolidated Markdown report tabulating every benchmark's base weight, the
+// components with a non-zero ref_time slope and that slope, base reads/writes, and the ref_time
+// R², grouped by pallet. Reads, writes and proof-size slopes are not listed. Gives reviewers an
+// at-a-glance, diff-friendly summary of a benchmark run without opening dozens of generated
+// `.rs` files or mentally decoding the weight formulas.
+fn write_markdown_report(
+	all_results: &HashMap<String, HashMap<String, BenchmarkData>>,
+	path: &PathBuf,
+) -> Result<(), std::io::Error> {
+	use std::io::Write;
+
+	let mut file_path = path.clone();
+	if file_path.file_name().is_none() {
+		file_path.push("benchmarks");
+	}
+	file_path.set_extension("md");
+
+	let mut file = fs::File::create(file_path)?;
+	writeln!(file, "# Benchmark Summary")?;
+
+	let mut pallets = all_results.keys().collect::<Vec<_>>();
+	pallets.sort();
+	for pallet in pallets {
+		writeln!(file, "\n## `{}`\n", pallet)?;
+		writeln!(file, "| Benchmark | Base Weight | Components (slope) | Base Reads | Base Writes | R\u{b2} |")?;
+		writeln!(file, "|---|---|---|---|---|---|")?;
+
+		let benchmarks = &all_results[pallet];
+		let mut benchmark_names = benchmarks.keys().collect::<Vec<_>>();
+		benchmark_names.sort();
+		for benchmark in benchmark_names {
+			let data = &benchmarks[benchmark];
+			let components = if data.component_weight.is_empty() {
+				"-".to_string()
+			} else {
+				data.component_weight.iter()
+					.map(|slope| format!("`{}` ({})", slope.name, slope.slope))
+					.collect::<Vec<_>>()
+					.join(", ")
+			};
+			writeln!(
+				file, "| `{}` | {} | {} | {} | {} | {:.2} |",
+				benchmark, data.base_weight, components, data.base_reads, data.base_writes,
+				data.weight_quality.r_squared,
+			)?;
+		}
+	}
+	Ok(())
+}
+
+// The percentage change from `old` to `new`. A previous value of zero has no ratio to compare
+// against, so any growth off of it is reported as an unbounded change rather than a silent 0%,
+// which would otherwise let a weight or slope jump from 0 without ever tripping the regression
+// gate below.
+fn percent_change(old: u128, new: u128) -> f64 {
+	if old == 0 {
+		if new == 0 { 0.0 } else { f64::INFINITY }
+	} else {
+		(new as f64 - old as f64) / old as f64 * 100.0
+	}
+}
+
+// Percentage change of each component's slope in `new` against its counterpart in `old`, by
+// component name. A component absent from `old` (e.g. a previously-unused component that has
+// now picked up a slope) is compared against an implicit old slope of `0`, the same treatment
+// `percent_change` already gives a zero `base_weight` - otherwise a component going from unused
+// to driving the benchmark's cost would sail through this gate unreported.
+fn slope_percent_changes<'a>(
+	old: &'a [ComponentSlope],
+	new: &'a [ComponentSlope],
+) -> Vec<(&'a str, f64)> {
+	new.iter()
+		.map(|new_slope| {
+			let old_slope = old.iter().find(|old_slope| old_slope.name == new_slope.name)
+				.map(|old_slope| old_slope.slope)
+				.unwrap_or(0);
+			(new_slope.name.as_str(), percent_change(old_slope, new_slope.slope))
+		})
+		.collect()
+}
+
+// Print the percentage change from `old` to `new` in base weight and in every used component's
+// slope, across all four cost dimensions, and return whether every one of them is within
+// `threshold_percent`. A benchmark's constant cost can stay flat while a per-item slope grows
+// (e.g. a heavier loop body), so each dimension's used component slopes are gated alongside the
+// base weight, not just the base weight on its own.
+fn benchmark_within_threshold(
+	pallet: &str,
+	benchmark: &str,
+	old_data: &BenchmarkData,
+	new_data: &BenchmarkData,
+	threshold_percent: f64,
+) -> bool {
+	let mut within_threshold = true;
+
+	let base_change = percent_change(old_data.base_weight, new_data.base_weight);
+	println!(
+		"{}::{}: {} -> {} ({:+.2}%)",
+		pallet, benchmark, old_data.base_weight, new_data.base_weight, base_change,
+	);
+	if base_change > threshold_percent {
+		println!("  ^ regression: exceeds threshold of {:.2}%", threshold_percent);
+		within_threshold = false;
+	}
+
+	let dimensions = [
+		("ref_time", &old_data.component_weight, &new_data.component_weight),
+		("reads", &old_data.component_reads, &new_data.component_reads),
+		("writes", &old_data.component_writes, &new_data.component_writes),
+		("proof_size", &old_data.component_proof_size, &new_data.component_proof_size),
+	];
+	for (dimension, old_slopes, new_slopes) in dimensions {
+		for (component, slope_change) in slope_percent_changes(old_slopes, new_slopes) {
+			println!(
+				"{}::{} [{}, component `{}`]: ({:+.2}%)",
+				pallet, benchmark, dimension, component, slope_change,
+			);
+			if slope_change > threshold_percent {
+				println!("  ^ regression: exceeds threshold of {:.2}%", threshold_percent);
+				within_threshold = false;
+			}
+		}
+	}
+
+	within_threshold
+}
+
+// Compare freshly computed benchmark results against a previously generated JSON benchmark
+// archive (as produced by `OutputFormat::Json`), printing the percentage change in base weight
+// and in every used component's slope, across all four cost dimensions, for every benchmark
+// present in both runs.
+//
+// `old_results_path` must be a single JSON archive, not a directory of generated `.rs` files:
+// the per-pallet Rust output carries no structured base weight/slope data to diff against, so a
+// repo must opt into archiving `--output-format json` runs before this gate can compare against
+// its committed weights.
+//
+// Returns `Ok(true)` if every benchmark is within `threshold_percent` of its old values,
+// `Ok(false)` if any benchmark regressed beyond it. Callers should exit non-zero in the latter
+// case so this can gate a CI pipeline, mirroring how hosted benchmark runners flag performance
+// changes on each commit rather than only regenerating numbers blindly.
+pub fn compare_results(
+	batches: &[BenchmarkBatch],
+	pov_modes: &PovModesMap,
+	old_results_path: &PathBuf,
+	cmd: &BenchmarkCmd,
+	threshold_percent: f64,
+) -> Result<bool, std::io::Error> {
+	let new_results = map_results(batches, pov_modes, &cmd.analysis_choice)?;
+
+	if old_results_path.is_dir() {
+		return Err(io_error(&format!(
+			"`{}` is a directory; --old-results must point to a single JSON archive produced by \
+			a previous run with --output-format json, not a directory of generated `.rs` files",
+			old_results_path.display(),
+		)))
+	}
+	let old_file = fs::File::open(old_results_path)?;
+	let old_results: HashMap<String, HashMap<String, BenchmarkData>> =
+		serde_json::from_reader(old_file).map_err(|e| io_error(&e.to_string()))?;
+
+	let mut all_within_threshold = true;
+	for (pallet, benchmarks) in &new_results {
+		let old_benchmarks = match old_results.get(pallet) {
+			Some(b) => b,
+			None => {
+				println!("pallet `{}` has no previous results, skipping", pallet);
+				continue
+			},
+		};
+
+		for (benchmark, new_data) in benchmarks {
+			let old_data = match old_benchmarks.get(benchmark) {
+				Some(d) => d,
+				None => {
+					println!("benchmark `{}::{}` has no previous results, skipping", pallet, benchmark);
+					continue
+				},
+			};
+
+			if !benchmark_within_threshold(pallet, benchmark, old_data, new_data, threshold_percent) {
+				all_within_threshold = false;
+			}
+		}
+	}
+
+	Ok(all_within_threshold)
+}
+
 // Add an underscore after every 3rd character, i.e. a separator for large numbers.
 fn underscore<Number>(i: Number) -> String
 	where Number: std::string::ToString
@@ -345,6 +970,7 @@ mod test {
 					repeat_reads: 0,
 					writes: (base + slope * i).into(),
 					repeat_writes: 0,
+					proof_size: (base + slope * i).into(),
 				}
 			)
 		}
@@ -359,10 +985,14 @@ mod test {
 
 	#[test]
 	fn map_results_works() {
-		let mapped_results = map_results(&[
-			test_data(b"first".to_vec(), BenchmarkParameter::a, 10, 3),
-			test_data(b"second".to_vec(), BenchmarkParameter::b, 3, 4),
-		]).unwrap();
+		let mapped_results = map_results(
+			&[
+				test_data(b"first".to_vec(), BenchmarkParameter::a, 10, 3),
+				test_data(b"second".to_vec(), BenchmarkParameter::b, 3, 4),
+			],
+			&PovModesMap::new(),
+			&AnalysisChoice::default(),
+		).unwrap();
 
 		let first_benchmark = mapped_results.get("first_pallet").unwrap().get("first_name").unwrap();
 
@@ -420,4 +1050,283 @@ mod test {
 			vec![ComponentSlope { name: "b".to_string(), slope: 4 }]
 		);
 	}
+
+	#[test]
+	fn percent_change_flags_growth_from_zero() {
+		// A previous value of 0 must not be reported (and thus gated) as "no change".
+		assert_eq!(percent_change(0, 0), 0.0);
+		assert!(percent_change(0, 1).is_infinite());
+		assert_eq!(percent_change(100, 150), 50.0);
+		assert_eq!(percent_change(100, 50), -50.0);
+	}
+
+	#[test]
+	fn slope_percent_changes_compares_by_component_name_only() {
+		let old = vec![
+			ComponentSlope { name: "a".to_string(), slope: 10 },
+			ComponentSlope { name: "b".to_string(), slope: 20 },
+		];
+		let new = vec![
+			ComponentSlope { name: "a".to_string(), slope: 10 },
+			ComponentSlope { name: "b".to_string(), slope: 40 },
+			ComponentSlope { name: "c".to_string(), slope: 5 },
+		];
+
+		let changes = slope_percent_changes(&old, &new);
+		assert_eq!(changes.iter().find(|(name, _)| *name == "a").unwrap().1, 0.0);
+		assert_eq!(changes.iter().find(|(name, _)| *name == "b").unwrap().1, 100.0);
+		// `c` has no matching component in `old`, so it's compared against an implicit slope of
+		// 0 - a previously-unused component picking up a slope is exactly the regression this
+		// gate exists to catch, so it must be reported rather than silently skipped.
+		assert!(changes.iter().find(|(name, _)| *name == "c").unwrap().1.is_infinite());
+	}
+
+	#[test]
+	fn benchmark_within_threshold_catches_a_slope_only_regression() {
+		// Base weight is untouched, but the `ref_time` slope on component `a` triples - e.g. a
+		// PR that leaves the benchmark's fixed overhead alone but makes its loop body heavier.
+		let old_data = BenchmarkData {
+			base_weight: 1_000,
+			component_weight: vec![ComponentSlope { name: "a".to_string(), slope: 100 }],
+			..Default::default()
+		};
+		let new_data = BenchmarkData {
+			base_weight: 1_000,
+			component_weight: vec![ComponentSlope { name: "a".to_string(), slope: 300 }],
+			..Default::default()
+		};
+
+		assert!(!benchmark_within_threshold("pallet", "bench", &old_data, &new_data, 10.0));
+		// A threshold generous enough to cover a 200% slope regression passes.
+		assert!(benchmark_within_threshold("pallet", "bench", &old_data, &new_data, 200.0));
+	}
+
+	#[test]
+	fn write_results_csv_includes_reads_and_writes_slopes() {
+		let all_results = map_results(
+			&[test_data(b"first".to_vec(), BenchmarkParameter::a, 10, 3)],
+			&PovModesMap::new(),
+			&AnalysisChoice::default(),
+		).unwrap();
+
+		let path = std::env::temp_dir().join("substrate_benchmarking_cli_write_results_csv_test.csv");
+		write_results_csv(&all_results, &path).unwrap();
+		let csv = fs::read_to_string(&path).unwrap();
+		fs::remove_file(&path).unwrap();
+
+		let mut lines = csv.lines();
+		assert_eq!(lines.next().unwrap(), "pallet,benchmark,dimension,component,base,slope");
+		let rows = lines.collect::<Vec<_>>();
+		// One row per used component per dimension: ref_time, reads, writes, and proof_size all
+		// have a slope on component `a`, so all four dimensions must be represented.
+		assert!(rows.iter().any(|r| r == &"first_pallet,first_name,ref_time,a,10000,3000"));
+		assert!(rows.iter().any(|r| r == &"first_pallet,first_name,reads,a,10,3"));
+		assert!(rows.iter().any(|r| r == &"first_pallet,first_name,writes,a,10,3"));
+		assert!(rows.iter().any(|r| r == &"first_pallet,first_name,proof_size,a,10,3"));
+	}
+
+	#[test]
+	fn write_results_json_round_trips() {
+		let all_results = map_results(
+			&[test_data(b"first".to_vec(), BenchmarkParameter::a, 10, 3)],
+			&PovModesMap::new(),
+			&AnalysisChoice::default(),
+		).unwrap();
+
+		let path = std::env::temp_dir().join("substrate_benchmarking_cli_write_results_json_test.json");
+		write_results_json(&all_results, &path).unwrap();
+		let file = fs::File::open(&path).unwrap();
+		let read_back: HashMap<String, HashMap<String, BenchmarkData>> =
+			serde_json::from_reader(file).unwrap();
+		fs::remove_file(&path).unwrap();
+
+		assert_eq!(
+			read_back.get("first_pallet").unwrap().get("first_name").unwrap().base_weight,
+			10_000,
+		);
+	}
+
+	#[test]
+	fn write_results_csv_and_json_are_deterministically_ordered() {
+		// Multiple pallets, inserted in reverse-alphabetical order: a `HashMap`'s iteration
+		// order would otherwise make two archives of the same run diff spuriously.
+		let all_results = map_results(
+			&[
+				test_data(b"second".to_vec(), BenchmarkParameter::b, 3, 4),
+				test_data(b"first".to_vec(), BenchmarkParameter::a, 10, 3),
+			],
+			&PovModesMap::new(),
+			&AnalysisChoice::default(),
+		).unwrap();
+
+		let csv_path = std::env::temp_dir().join("substrate_benchmarking_cli_write_results_csv_order_test.csv");
+		write_results_csv(&all_results, &csv_path).unwrap();
+		let csv = fs::read_to_string(&csv_path).unwrap();
+		fs::remove_file(&csv_path).unwrap();
+		let first_pos = csv.find("first_pallet").unwrap();
+		let second_pos = csv.find("second_pallet").unwrap();
+		assert!(first_pos < second_pos, "rows must be sorted by pallet, then benchmark");
+
+		let json_path = std::env::temp_dir().join("substrate_benchmarking_cli_write_results_json_order_test.json");
+		write_results_json(&all_results, &json_path).unwrap();
+		let json = fs::read_to_string(&json_path).unwrap();
+		fs::remove_file(&json_path).unwrap();
+		let first_pos = json.find("first_pallet").unwrap();
+		let second_pos = json.find("second_pallet").unwrap();
+		assert!(first_pos < second_pos, "keys must be sorted by pallet, then benchmark");
+	}
+
+	#[test]
+	fn median_slopes_fits_a_clean_linear_series() {
+		let batch = test_data(b"first".to_vec(), BenchmarkParameter::a, 10, 3);
+		let fit = median_slopes(&batch.results, BenchmarkSelector::ExtrinsicTime);
+
+		assert_eq!(fit.names, vec!["a".to_string(), "z".to_string()]);
+		assert_eq!(fit.base, 10);
+		assert_eq!(fit.slopes, vec![3, 0]);
+	}
+
+	#[test]
+	fn max_observed_uses_the_slowest_sample_with_zero_slopes() {
+		let batch = test_data(b"first".to_vec(), BenchmarkParameter::a, 10, 3);
+		let fit = max_observed(&batch.results, BenchmarkSelector::ExtrinsicTime);
+
+		// Samples run from `base` (i=0) to `base + slope * 4` (i=4).
+		assert_eq!(fit.base, 10 + 3 * 4);
+		assert_eq!(fit.slopes, vec![0, 0]);
+	}
+
+	#[test]
+	fn map_results_honours_the_selected_analysis_choice() {
+		let min_squares_results = map_results(
+			&[test_data(b"first".to_vec(), BenchmarkParameter::a, 10, 3)],
+			&PovModesMap::new(), &AnalysisChoice::MinSquares,
+		).unwrap();
+		let median_slopes_results = map_results(
+			&[test_data(b"first".to_vec(), BenchmarkParameter::a, 10, 3)],
+			&PovModesMap::new(), &AnalysisChoice::MedianSlopes,
+		).unwrap();
+		let max_observed_results = map_results(
+			&[test_data(b"first".to_vec(), BenchmarkParameter::a, 10, 3)],
+			&PovModesMap::new(), &AnalysisChoice::MaxObserved,
+		).unwrap();
+
+		let weight = |m: &HashMap<String, HashMap<String, BenchmarkData>>| {
+			m.get("first_pallet").unwrap().get("first_name").unwrap().base_weight
+		};
+		assert_eq!(weight(&min_squares_results), 10_000);
+		assert_eq!(weight(&median_slopes_results), 10_000);
+		assert_eq!(weight(&max_observed_results), (10 + 3 * 4) * 1000);
+	}
+
+	#[test]
+	fn ignored_pov_modes_are_dropped() {
+		let batch = test_data(b"first".to_vec(), BenchmarkParameter::a, 10, 3);
+		let mut pov_modes = HashMap::new();
+		pov_modes.insert("Measured1".to_string(), PovEstimationMode::Measured);
+		pov_modes.insert("Ignored1".to_string(), PovEstimationMode::Ignored);
+
+		let data = get_benchmark_data(&batch, &pov_modes, &AnalysisChoice::default());
+
+		assert_eq!(data.pov_modes.len(), 1);
+		assert_eq!(data.pov_modes.get("Measured1").unwrap(), "Measured");
+		assert!(data.pov_modes.get("Ignored1").is_none());
+	}
+
+	#[test]
+	fn map_results_looks_up_pov_modes_by_pallet_not_by_benchmark() {
+		// `PovModesMap` is keyed by pallet only: `StorageInfo` has no notion of which benchmark
+		// touches which prefix, so every benchmark in `first_pallet` must see the same modes,
+		// and a real-world key (the pallet name) - not the benchmark name - must hit.
+		let mut pov_modes = PovModesMap::new();
+		let mut modes = HashMap::new();
+		modes.insert("Measured1".to_string(), PovEstimationMode::Measured);
+		pov_modes.insert("first_pallet".to_string(), modes);
+
+		let mapped_results = map_results(
+			&[test_data(b"first".to_vec(), BenchmarkParameter::a, 10, 3)],
+			&pov_modes,
+			&AnalysisChoice::default(),
+		).unwrap();
+
+		let data = mapped_results.get("first_pallet").unwrap().get("first_name").unwrap();
+		assert_eq!(data.pov_modes.get("Measured1").unwrap(), "Measured");
+	}
+
+	#[test]
+	fn write_markdown_report_lists_every_pallet_and_benchmark() {
+		let all_results = map_results(
+			&[
+				test_data(b"first".to_vec(), BenchmarkParameter::a, 10, 3),
+				test_data(b"second".to_vec(), BenchmarkParameter::b, 3, 4),
+			],
+			&PovModesMap::new(),
+			&AnalysisChoice::default(),
+		).unwrap();
+
+		let path = std::env::temp_dir().join("substrate_benchmarking_cli_write_markdown_report_test.md");
+		write_markdown_report(&all_results, &path).unwrap();
+		let markdown = fs::read_to_string(&path).unwrap();
+		fs::remove_file(&path).unwrap();
+
+		assert!(markdown.contains("# Benchmark Summary"));
+		assert!(markdown.contains("`first_pallet`"));
+		assert!(markdown.contains("`second_pallet`"));
+		assert!(markdown.contains("`first_name`"));
+		assert!(markdown.contains("`a` (3000)"));
+	}
+
+	// Like `test_data`, but the response values are given directly instead of being derived
+	// from a clean `base + slope * i` line, so the fitted regression can be made a poor one.
+	fn noisy_test_data(name: Vec<u8>, param: BenchmarkParameter, values: Vec<u32>) -> BenchmarkBatch {
+		let results = values.iter().enumerate().map(|(i, value)| {
+			BenchmarkResults {
+				components: vec![(param, i as u32), (BenchmarkParameter::z, 0)],
+				extrinsic_time: *value,
+				storage_root_time: *value,
+				reads: *value,
+				repeat_reads: 0,
+				writes: *value,
+				repeat_writes: 0,
+				proof_size: *value,
+			}
+		}).collect();
+
+		BenchmarkBatch {
+			pallet: [name.clone(), b"_pallet".to_vec()].concat(),
+			benchmark: [name, b"_name".to_vec()].concat(),
+			results,
+		}
+	}
+
+	#[test]
+	fn regression_quality_reflects_fit_quality() {
+		let good = test_data(b"good".to_vec(), BenchmarkParameter::a, 10, 3);
+		let good_fit = analyze(&AnalysisChoice::MinSquares, &good.results, BenchmarkSelector::ExtrinsicTime);
+		let good_quality = regression_quality(&good.results, BenchmarkSelector::ExtrinsicTime, &good_fit);
+		assert!(good_quality.r_squared > 0.99);
+
+		// Bounces around instead of following the component, so a linear fit explains almost
+		// none of the variance.
+		let noisy = noisy_test_data(b"noisy".to_vec(), BenchmarkParameter::a, vec![10, 50, 5, 60, 8]);
+		let noisy_fit = analyze(&AnalysisChoice::MinSquares, &noisy.results, BenchmarkSelector::ExtrinsicTime);
+		let noisy_quality = regression_quality(&noisy.results, BenchmarkSelector::ExtrinsicTime, &noisy_fit);
+		assert!(noisy_quality.r_squared < 0.5);
+	}
+
+	#[test]
+	fn check_regression_quality_respects_threshold_and_strict_mode() {
+		let all_results = map_results(
+			&[noisy_test_data(b"noisy".to_vec(), BenchmarkParameter::a, vec![10, 50, 5, 60, 8])],
+			&PovModesMap::new(),
+			&AnalysisChoice::MinSquares,
+		).unwrap();
+
+		// Non-strict: a poor fit only warns, the call still succeeds.
+		assert!(check_regression_quality(&all_results, 0.99, false).is_ok());
+		// Strict: the same poor fit now fails the gate.
+		assert!(check_regression_quality(&all_results, 0.99, true).is_err());
+		// A generous threshold passes even in strict mode.
+		assert!(check_regression_quality(&all_results, 0.0, true).is_ok());
+	}
 }